@@ -0,0 +1,47 @@
+//! Runtime registry of grammars compiled from vendored sources by `build.rs`,
+//! replacing the hand-written `unsafe extern "C"` declarations and dummy
+//! link constants that `src/syntax.rs` used to need for languages whose
+//! crates don't export a usable Rust API.
+
+/// Static description of a compiled grammar: its `Language` constructor and
+/// everything `SyntaxHighlighter` needs to highlight it.
+pub struct GrammarDescriptor {
+    pub name: &'static str,
+    language: unsafe extern "C" fn() -> tree_sitter::Language,
+    pub highlights_query: &'static str,
+    pub injections_query: Option<&'static str>,
+    pub locals_query: Option<&'static str>,
+    pub extensions: &'static [&'static str],
+    pub filenames: &'static [&'static str],
+    pub injection_name: &'static str,
+}
+
+impl GrammarDescriptor {
+    /// Build the `tree_sitter::Language` for this grammar.
+    pub fn language(&self) -> tree_sitter::Language {
+        unsafe { (self.language)() }
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/grammars.rs"));
+
+/// Looks up compiled grammars by name, extension, or filename.
+pub struct GrammarRegistry;
+
+impl GrammarRegistry {
+    pub fn by_name(name: &str) -> Option<&'static GrammarDescriptor> {
+        GRAMMARS.iter().find(|g| g.name == name)
+    }
+
+    pub fn by_extension(ext: &str) -> Option<&'static GrammarDescriptor> {
+        let ext = ext.to_lowercase();
+        GRAMMARS.iter().find(|g| g.extensions.contains(&ext.as_str()))
+    }
+
+    pub fn by_filename(filename: &str) -> Option<&'static GrammarDescriptor> {
+        let filename = filename.to_lowercase();
+        GRAMMARS
+            .iter()
+            .find(|g| g.filenames.contains(&filename.as_str()))
+    }
+}
@@ -1,34 +1,26 @@
 use crate::framebuffer::{Framebuffer, IndexedColor};
+use crate::grammar_registry::GrammarRegistry;
 use crate::oklab::StraightRgba;
+use std::collections::HashMap;
 use std::sync::Arc;
 use tree_sitter::{Parser, Tree};
 use tree_sitter_highlight::{HighlightConfiguration, HighlightEvent, Highlighter};
 
-// Embedded highlight queries for languages that don't export them in their crates
+// Rainbow bracket/delimiter queries (Helix `rainbows.scm`-style), embedded
+// for the languages that currently support the feature. `@rainbow.scope`
+// marks nodes that increase nesting depth; `@rainbow.bracket` marks the
+// delimiter tokens to color by that depth.
+const RUST_RAINBOWS: &str = include_str!("../queries/rust_rainbows.scm");
+const JAVASCRIPT_RAINBOWS: &str = include_str!("../queries/javascript_rainbows.scm");
+const PYTHON_RAINBOWS: &str = include_str!("../queries/python_rainbows.scm");
+const JSON_RAINBOWS: &str = include_str!("../queries/json_rainbows.scm");
 
-const KOTLIN_HIGHLIGHTS: &str = include_str!("../queries/kotlin_highlights.scm");
-const SQL_HIGHLIGHTS: &str = include_str!("../queries/sql_highlights.scm");
-const DOCKERFILE_HIGHLIGHTS: &str = include_str!("../queries/dockerfile_highlights.scm");
-const MARKDOWN_HIGHLIGHTS: &str = include_str!("../queries/markdown_highlights.scm");
-
-// External C functions for languages with version incompatibility
-// These crates use older tree-sitter versions, so we call the C functions directly
-unsafe extern "C" {
-    fn tree_sitter_kotlin() -> tree_sitter::Language;
-    fn tree_sitter_markdown() -> tree_sitter::Language;
-    fn tree_sitter_sql() -> tree_sitter::Language;
-    fn tree_sitter_dockerfile() -> tree_sitter::Language;
-}
-
-// Dummy references to ensure the crates' C code gets linked
-#[cfg(feature = "syntax-kotlin")]
-const _KOTLIN_LINK: &str = tree_sitter_kotlin::NODE_TYPES;
-#[cfg(feature = "syntax-markdown")]
-const _MARKDOWN_LINK: &str = tree_sitter_markdown::NODE_TYPES;
-#[cfg(feature = "syntax-sql")]
-const _SQL_LINK: &str = tree_sitter_sql::NODE_TYPES;
-#[cfg(feature = "syntax-dockerfile")]
-const _DOCKERFILE_LINK: &str = tree_sitter_dockerfile::NODE_TYPES;
+// Kotlin, Markdown, SQL, and Dockerfile previously needed a hand-written
+// `unsafe extern "C"` block plus a dummy `#[cfg(feature = "...")]` link
+// constant here, because their crates pin an older tree-sitter version. That
+// wiring now lives in `build.rs`/`grammar_registry`, which compiles their
+// vendored grammar sources directly and exposes them through
+// `GrammarRegistry` like any other grammar.
 
 /// Represents a highlighted span in the source code
 #[derive(Debug, Clone)]
@@ -39,7 +31,7 @@ pub struct HighlightSpan {
 }
 
 /// Supported programming languages
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Language {
     // Programming languages
     Rust,
@@ -74,9 +66,14 @@ pub enum Language {
 }
 
 impl Language {
-    /// Detect language from file extension
+    /// Detect language from file extension.
+    ///
+    /// Languages compiled through `GrammarRegistry` (see `from_registry_name`)
+    /// aren't hardcoded here: their extensions come from the registry so
+    /// adding one of those doesn't require touching this match.
     pub fn from_extension(ext: &str) -> Option<Self> {
-        match ext.to_lowercase().as_str() {
+        let ext_lower = ext.to_lowercase();
+        let from_static_table = match ext_lower.as_str() {
             // Rust
             "rs" => Some(Language::Rust),
             // Python
@@ -97,8 +94,6 @@ impl Language {
             "rb" | "rake" | "gemspec" => Some(Language::Ruby),
             // PHP
             "php" | "phtml" | "php3" | "php4" | "php5" | "php7" | "phps" => Some(Language::Php),
-            // Kotlin
-            "kt" | "kts" => Some(Language::Kotlin),
             // Scala
             "scala" | "sc" => Some(Language::Scala),
             // Haskell
@@ -111,29 +106,310 @@ impl Language {
             "html" | "htm" => Some(Language::Html),
             "css" => Some(Language::Css),
             // Markup and data
-            "md" | "markdown" | "mkd" | "mkdn" => Some(Language::Markdown),
             "json" | "jsonc" => Some(Language::Json),
             "yaml" | "yml" => Some(Language::Yaml),
             "toml" => Some(Language::Toml),
             "xml" | "xsl" | "xsd" | "svg" => Some(Language::Xml),
             // Shell
             "sh" | "bash" | "zsh" => Some(Language::Bash),
-            // SQL
-            "sql" | "mysql" | "pgsql" => Some(Language::Sql),
             _ => None,
-        }
+        };
+
+        from_static_table.or_else(|| {
+            GrammarRegistry::by_extension(&ext_lower)
+                .and_then(|grammar| Language::from_registry_name(grammar.name))
+        })
     }
 
-    /// Detect language from filename (for special files without extensions)
+    /// Detect language from filename (for special files without extensions).
     pub fn from_filename(filename: &str) -> Option<Self> {
-        match filename.to_lowercase().as_str() {
-            "dockerfile" | "containerfile" => Some(Language::Dockerfile),
+        let filename_lower = filename.to_lowercase();
+        let from_static_table = match filename_lower.as_str() {
             "makefile" | "gnumakefile" => Some(Language::Bash), // Makefile uses shell syntax often
             "rakefile" | "gemfile" => Some(Language::Ruby),
             _ => None,
+        };
+
+        from_static_table.or_else(|| {
+            GrammarRegistry::by_filename(&filename_lower)
+                .and_then(|grammar| Language::from_registry_name(grammar.name))
+        })
+    }
+
+    /// Map a `GrammarRegistry` entry's name back to its `Language` variant.
+    /// Only covers the languages currently routed through the registry
+    /// (see `grammar_language_config`); other languages get their
+    /// `tree_sitter::Language` directly from their own crate instead.
+    fn from_registry_name(name: &str) -> Option<Self> {
+        match name {
+            "kotlin" => Some(Language::Kotlin),
+            "markdown" => Some(Language::Markdown),
+            "sql" => Some(Language::Sql),
+            "dockerfile" => Some(Language::Dockerfile),
+            _ => None,
+        }
+    }
+
+    /// Map the language-name string captured by an `@injection.language` node
+    /// (e.g. the `js`/`javascript` in an HTML `<script>` tag, or the fenced
+    /// code block info string in Markdown) to a `Language`.
+    pub fn from_injection_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "rust" => Some(Language::Rust),
+            "python" | "py" => Some(Language::Python),
+            "javascript" | "js" => Some(Language::JavaScript),
+            "typescript" | "ts" => Some(Language::TypeScript),
+            "c" => Some(Language::C),
+            "cpp" | "c++" => Some(Language::Cpp),
+            "swift" => Some(Language::Swift),
+            "go" | "golang" => Some(Language::Go),
+            "java" => Some(Language::Java),
+            "ruby" | "rb" => Some(Language::Ruby),
+            "php" => Some(Language::Php),
+            "kotlin" | "kt" => Some(Language::Kotlin),
+            "scala" => Some(Language::Scala),
+            "haskell" | "hs" => Some(Language::Haskell),
+            "elixir" | "ex" => Some(Language::Elixir),
+            "zig" => Some(Language::Zig),
+            "html" => Some(Language::Html),
+            "css" => Some(Language::Css),
+            "markdown" | "md" => Some(Language::Markdown),
+            "json" => Some(Language::Json),
+            "yaml" | "yml" => Some(Language::Yaml),
+            "toml" => Some(Language::Toml),
+            "xml" => Some(Language::Xml),
+            "bash" | "sh" | "shell" => Some(Language::Bash),
+            "sql" => Some(Language::Sql),
+            "dockerfile" => Some(Language::Dockerfile),
+            _ => None,
+        }
+    }
+
+    /// Detect a language from its content when extension and filename
+    /// lookups both come back empty: a shebang on the first line
+    /// (`#!/usr/bin/env python3`), or an editor modeline near the top or
+    /// bottom of the file (`# vim: set ft=rust:`, `-*- mode: python -*-`).
+    /// Callers should treat this as a last-resort fallback after
+    /// `from_extension`/`from_filename`, the way it makes extensionless
+    /// scripts and dotfiles classifiable.
+    pub fn from_content(first_lines: &str) -> Option<Self> {
+        let mut lines = first_lines.lines();
+        if let Some(first) = lines.next() {
+            if let Some(interpreter_line) = first.strip_prefix("#!") {
+                if let Some(lang) = Self::from_shebang(interpreter_line.trim()) {
+                    return Some(lang);
+                }
+            }
+        }
+
+        // Modelines conventionally live in the first few or last few lines.
+        let all_lines: Vec<&str> = first_lines.lines().collect();
+        let head = all_lines.iter().take(5);
+        let tail = all_lines.iter().rev().take(5);
+        for line in head.chain(tail) {
+            if let Some(lang) = Self::from_modeline(line) {
+                return Some(lang);
+            }
+        }
+
+        None
+    }
+
+    /// Map a shebang's interpreter (after stripping `#!` and any `env`
+    /// indirection) to a language, e.g. `/usr/bin/env python3` -> `Python`.
+    fn from_shebang(interpreter_line: &str) -> Option<Self> {
+        let mut parts = interpreter_line.split_whitespace();
+        let mut program = parts.next()?;
+        if program.rsplit('/').next() == Some("env") {
+            program = parts.next()?;
+        }
+        let basename = program.rsplit('/').next().unwrap_or(program);
+        let name = basename.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+
+        match name {
+            "python" => Some(Language::Python),
+            "bash" | "sh" | "zsh" | "dash" | "ksh" => Some(Language::Bash),
+            "node" | "nodejs" => Some(Language::JavaScript),
+            "ruby" => Some(Language::Ruby),
+            "php" => Some(Language::Php),
+            _ => None,
+        }
+    }
+
+    /// Check a single line for a Vim (`vim: set ft=...:`) or Emacs
+    /// (`-*- mode: ... -*-`) modeline and map its declared filetype.
+    fn from_modeline(line: &str) -> Option<Self> {
+        if let Some(idx) = line.find("vim:") {
+            let rest = &line[idx + "vim:".len()..];
+            for token in rest.split([':', ' ', ',']) {
+                let filetype = token
+                    .strip_prefix("ft=")
+                    .or_else(|| token.strip_prefix("filetype="));
+                if let Some(filetype) = filetype {
+                    if let Some(lang) = Self::from_vim_filetype(filetype) {
+                        return Some(lang);
+                    }
+                }
+            }
+        }
+
+        if let Some(start) = line.find("-*-") {
+            let after = &line[start + "-*-".len()..];
+            if let Some(end) = after.find("-*-") {
+                for part in after[..end].split(';') {
+                    let part = part.trim();
+                    let mode = part
+                        .strip_prefix("mode:")
+                        .or_else(|| part.strip_prefix("Mode:"))
+                        .map(str::trim)
+                        .or(if part.contains(':') { None } else { Some(part) });
+                    if let Some(mode) = mode {
+                        if let Some(lang) = Self::from_emacs_mode(mode) {
+                            return Some(lang);
+                        }
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn from_vim_filetype(filetype: &str) -> Option<Self> {
+        match filetype {
+            "rust" => Some(Language::Rust),
+            "python" => Some(Language::Python),
+            "javascript" => Some(Language::JavaScript),
+            "typescript" => Some(Language::TypeScript),
+            "sh" | "bash" => Some(Language::Bash),
+            "ruby" => Some(Language::Ruby),
+            "html" => Some(Language::Html),
+            "css" => Some(Language::Css),
+            "markdown" => Some(Language::Markdown),
+            "yaml" => Some(Language::Yaml),
+            "json" => Some(Language::Json),
+            "toml" => Some(Language::Toml),
+            "sql" => Some(Language::Sql),
+            "dockerfile" => Some(Language::Dockerfile),
+            _ => None,
         }
     }
 
+    fn from_emacs_mode(mode: &str) -> Option<Self> {
+        match mode {
+            "rust" | "rustic" => Some(Language::Rust),
+            "python" => Some(Language::Python),
+            "js" | "js2" | "javascript" => Some(Language::JavaScript),
+            "typescript" => Some(Language::TypeScript),
+            "sh" => Some(Language::Bash),
+            "ruby" | "enh-ruby" => Some(Language::Ruby),
+            "html" => Some(Language::Html),
+            "css" => Some(Language::Css),
+            "markdown" | "gfm" => Some(Language::Markdown),
+            "yaml" => Some(Language::Yaml),
+            "json" => Some(Language::Json),
+            "sql" => Some(Language::Sql),
+            "dockerfile" => Some(Language::Dockerfile),
+            _ => None,
+        }
+    }
+}
+
+/// Standard highlight names (TextMate-compatible), shared by every language's
+/// `HighlightConfiguration`, including those built lazily for injections.
+const STANDARD_HIGHLIGHT_NAMES: &[&str] = &[
+    "attribute",
+    "comment",
+    "constant",
+    "constant.builtin",
+    "constructor",
+    "embedded",
+    "function",
+    "function.builtin",
+    "function.method",
+    "keyword",
+    "number",
+    "operator",
+    "property",
+    "punctuation",
+    "punctuation.bracket",
+    "punctuation.delimiter",
+    "string",
+    "string.special",
+    "tag",
+    "type",
+    "type.builtin",
+    "variable",
+    "variable.builtin",
+    "variable.parameter",
+];
+
+/// Build and configure a `HighlightConfiguration` for `language`.
+fn build_highlight_configuration(
+    language: Language,
+    highlight_names: &[String],
+) -> Result<HighlightConfiguration, String> {
+    let (tree_sitter_lang, highlight_query, injection_query, locals_query) =
+        get_language_config(language)?;
+
+    let mut config = HighlightConfiguration::new(
+        tree_sitter_lang,
+        "source", // scope name
+        highlight_query,
+        injection_query.unwrap_or(""),
+        locals_query.unwrap_or(""),
+    )
+    .map_err(|e| format!("Failed to create highlight configuration: {}", e))?;
+
+    config.configure(highlight_names);
+    Ok(config)
+}
+
+/// Lazily build and cache the `HighlightConfiguration` for an injected
+/// language, so nested regions (HTML's embedded `<script>`/`<style>`,
+/// Markdown fenced code blocks, etc.) get highlighted with their own scopes
+/// instead of being skipped.
+fn resolve_injection<'a>(
+    injection_configs: &'a mut HashMap<Language, Arc<HighlightConfiguration>>,
+    highlight_names: &[String],
+    injection_name: &str,
+) -> Option<&'a HighlightConfiguration> {
+    let language = Language::from_injection_name(injection_name)?;
+    if !injection_configs.contains_key(&language) {
+        let config = build_highlight_configuration(language, highlight_names).ok()?;
+        injection_configs.insert(language, Arc::new(config));
+    }
+    injection_configs.get(&language).map(|c| c.as_ref())
+}
+
+/// Reconciles cached highlight spans against a single incremental edit:
+/// drops spans that overlap a changed range, and shifts the byte offsets of
+/// spans that fall after the edit so they still line up with the new
+/// source.
+///
+/// `changed_ranges` is reported by `Tree::changed_ranges` against the *new*
+/// tree, so it's in post-edit byte coordinates. Spans are shifted into that
+/// same coordinate space before the overlap check runs — comparing a span's
+/// pre-edit offsets against post-edit changed ranges would miss overlaps for
+/// any span that falls after the edit.
+fn reconcile_spans(
+    spans: Vec<HighlightSpan>,
+    changed_ranges: &[(usize, usize)],
+    edit_old_end_byte: usize,
+    byte_delta: isize,
+) -> Vec<HighlightSpan> {
+    let mut spans = spans;
+    spans.retain_mut(|span| {
+        if span.start_byte >= edit_old_end_byte {
+            span.start_byte = (span.start_byte as isize + byte_delta) as usize;
+            span.end_byte = (span.end_byte as isize + byte_delta) as usize;
+        }
+        let overlaps_change = changed_ranges
+            .iter()
+            .any(|(start, end)| span.end_byte > *start && span.start_byte < *end);
+        !overlaps_change
+    });
+    spans
 }
 
 /// Manages syntax highlighting for a document
@@ -148,60 +424,36 @@ pub struct SyntaxHighlighter {
     // Cache of all highlight spans for the entire file
     highlight_cache: Vec<HighlightSpan>,
     highlight_cache_generation: u32,
+    // Byte ranges touched since the cache was last reconciled; empty means
+    // the cache is fully up to date for `highlight_cache_generation`.
+    damaged_ranges: Vec<(usize, usize)>,
+    // Highlight configurations for injected languages, built on first use
+    injection_configs: HashMap<Language, Arc<HighlightConfiguration>>,
+    // Rainbow bracket/delimiter mode (off by default)
+    pub rainbow_enabled: bool,
+    rainbow_query: Option<Arc<tree_sitter::Query>>,
+    rainbow_cache: Vec<HighlightSpan>,
+    // Generation the rainbow cache was computed for; `None` when it has never
+    // been computed or was cleared because rainbow mode was turned off.
+    rainbow_cache_generation: Option<u32>,
 }
 
 impl SyntaxHighlighter {
     /// Create a new syntax highlighter for the given language
     pub fn new(language: Language) -> Result<Self, String> {
         let mut parser = Parser::new();
-        let (tree_sitter_lang, highlight_query, injection_query, locals_query) =
-            get_language_config(language)?;
+        let (tree_sitter_lang, _, _, _) = get_language_config(language)?;
 
         parser
             .set_language(&tree_sitter_lang)
             .map_err(|e| format!("Failed to set parser language: {}", e))?;
 
-        let mut config = HighlightConfiguration::new(
-            tree_sitter_lang,
-            "source", // scope name
-            highlight_query,
-            injection_query.unwrap_or(""),
-            locals_query.unwrap_or(""),
-        )
-        .map_err(|e| format!("Failed to create highlight configuration: {}", e))?;
-
-        // Standard highlight names (TextMate-compatible)
-        let highlight_names = vec![
-            "attribute",
-            "comment",
-            "constant",
-            "constant.builtin",
-            "constructor",
-            "embedded",
-            "function",
-            "function.builtin",
-            "function.method",
-            "keyword",
-            "number",
-            "operator",
-            "property",
-            "punctuation",
-            "punctuation.bracket",
-            "punctuation.delimiter",
-            "string",
-            "string.special",
-            "tag",
-            "type",
-            "type.builtin",
-            "variable",
-            "variable.builtin",
-            "variable.parameter",
-        ]
-        .iter()
-        .map(|s| s.to_string())
-        .collect::<Vec<_>>();
-
-        config.configure(&highlight_names);
+        let highlight_names = STANDARD_HIGHLIGHT_NAMES
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+
+        let config = build_highlight_configuration(language, &highlight_names)?;
 
         Ok(Self {
             language,
@@ -213,65 +465,168 @@ impl SyntaxHighlighter {
             source_cache: Vec::new(),
             highlight_cache: Vec::new(),
             highlight_cache_generation: 0,
+            damaged_ranges: Vec::new(),
+            injection_configs: HashMap::new(),
+            rainbow_enabled: false,
+            rainbow_query: None,
+            rainbow_cache: Vec::new(),
+            rainbow_cache_generation: None,
         })
     }
 
-    /// Update the syntax tree if the buffer has changed
-    /// This caches the source buffer to avoid re-collecting it on every render
+    /// Replace the entire buffer and reparse it from scratch.
+    /// Use this for loads and language switches; for ordinary edits prefer
+    /// `apply_edit`, which reparses incrementally and only invalidates the
+    /// highlight spans that actually changed.
     pub fn update(&mut self, source: Vec<u8>, buffer_generation: u32) {
         if self.buffer_generation == buffer_generation && self.tree.is_some() {
             return; // Already up to date
         }
 
         self.tree = self.parser.parse(&source, None);
+        self.highlight_cache.clear();
+        self.damaged_ranges = vec![(0, source.len())];
         self.source_cache = source;
         self.buffer_generation = buffer_generation;
     }
 
+    /// Apply a single incremental edit and reparse using the previous tree.
+    ///
+    /// Tells tree-sitter about the edit via `InputEdit`, reparses with the
+    /// old tree as a starting point, and uses `Tree::changed_ranges` to find
+    /// exactly which byte ranges actually changed shape. Highlight spans
+    /// outside those ranges are kept (shifted to account for the edit's
+    /// length delta) instead of being thrown away. `ensure_highlights_cached`
+    /// still has to re-run `Highlighter` over the whole file to recompute the
+    /// changed ranges themselves (see `highlight_full_source` for why), but it only
+    /// adds back the spans that actually fall in a changed range, leaving
+    /// the retained spans from here alone.
+    pub fn apply_edit(
+        &mut self,
+        new_source: Vec<u8>,
+        edit: tree_sitter::InputEdit,
+        buffer_generation: u32,
+    ) {
+        let byte_delta = edit.new_end_byte as isize - edit.old_end_byte as isize;
+
+        let changed_ranges: Vec<(usize, usize)> = if let Some(mut old_tree) = self.tree.take() {
+            old_tree.edit(&edit);
+            let new_tree = self.parser.parse(&new_source, Some(&old_tree));
+            let ranges = match &new_tree {
+                Some(new_tree) => old_tree
+                    .changed_ranges(new_tree)
+                    .map(|r| (r.start_byte, r.end_byte))
+                    .collect(),
+                None => vec![(0, new_source.len())],
+            };
+            self.tree = new_tree;
+            ranges
+        } else {
+            self.tree = self.parser.parse(&new_source, None);
+            vec![(0, new_source.len())]
+        };
+
+        // Drop cached spans that overlap a changed range; everything else is
+        // retained, shifting spans that fall after the edit so their byte
+        // offsets still line up with `new_source`.
+        self.highlight_cache = reconcile_spans(
+            std::mem::take(&mut self.highlight_cache),
+            &changed_ranges,
+            edit.old_end_byte,
+            byte_delta,
+        );
+
+        self.damaged_ranges.extend(changed_ranges);
+        self.source_cache = new_source;
+        self.buffer_generation = buffer_generation;
+    }
+
     /// Get a reference to the cached source buffer
     pub fn cached_source(&self) -> &[u8] {
         &self.source_cache
     }
 
     /// Ensure highlights are cached for the current buffer generation
-    fn ensure_highlights_cached(&mut self, fb: &Framebuffer) {
-        // Check if cache is already up to date
-        if self.highlight_cache_generation == self.buffer_generation && !self.highlight_cache.is_empty() {
+    fn ensure_highlights_cached(&mut self, theme: &Theme, fb: &Framebuffer) {
+        // Check if cache is already up to date: same generation and nothing
+        // left to reconcile.
+        if self.highlight_cache_generation == self.buffer_generation && self.damaged_ranges.is_empty() {
             return;
         }
 
-        // Clear old cache
-        self.highlight_cache.clear();
-
         if self.tree.is_none() || self.source_cache.is_empty() {
+            self.highlight_cache.clear();
+            self.damaged_ranges.clear();
+            self.highlight_cache_generation = self.buffer_generation;
             return;
         }
 
-        // Compute highlights for entire file
-        let mut highlighter = Highlighter::new();
-        let highlight_iter = match highlighter.highlight(&self.config, &self.source_cache, None, |_| None) {
-            Ok(iter) => iter,
-            Err(_) => return,
+        let damaged = std::mem::take(&mut self.damaged_ranges);
+
+        let Some(fresh_spans) = self.highlight_full_source(&damaged, theme, fb) else {
+            return;
         };
 
+        self.highlight_cache.extend(fresh_spans);
+        self.highlight_cache_generation = self.buffer_generation;
+    }
+
+    /// Highlight pass via `tree_sitter_highlight::Highlighter`, filtered down
+    /// to the spans that fall in a damaged range.
+    ///
+    /// `Highlighter` always walks the whole source — it has no API to scope
+    /// a pass to a byte range — so this doesn't save the CPU cost of
+    /// recomputing on every edit; what it saves is only the cache churn
+    /// (untouched spans that `apply_edit` already retained aren't
+    /// recomputed or replaced). An earlier version of this used a raw
+    /// `QueryCursor::set_byte_range` to avoid the full walk, but that bypassed
+    /// `config.configure`'s capture filtering and `Highlighter`'s `#match?`/
+    /// `#eq?` predicate evaluation, so it painted `@local.*` and
+    /// `@injection.*` captures from the combined locals/injections/highlights
+    /// query as if they were highlight scopes. Going through `Highlighter`
+    /// for every recompute avoids that at the cost of the full-file walk,
+    /// until a scoped pass can filter captures and evaluate predicates
+    /// correctly.
+    fn highlight_full_source(
+        &mut self,
+        damaged: &[(usize, usize)],
+        theme: &Theme,
+        fb: &Framebuffer,
+    ) -> Option<Vec<HighlightSpan>> {
+        let config = &self.config;
+        let source_cache = &self.source_cache;
+        let injection_configs = &mut self.injection_configs;
+        let highlight_names = &self.highlight_names;
+
+        let mut highlighter = Highlighter::new();
+        let highlight_iter = highlighter
+            .highlight(config, source_cache, None, |name| {
+                resolve_injection(injection_configs, highlight_names, name)
+            })
+            .ok()?;
+
         let mut current_highlight: Option<usize> = None;
+        let mut fresh_spans = Vec::new();
 
         for event in highlight_iter {
             match event {
                 Ok(HighlightEvent::Source { start, end }) => {
                     if let Some(highlight_idx) = current_highlight {
-                        let color = get_highlight_color(
-                            self.highlight_names
-                                .get(highlight_idx)
-                                .map(|s| s.as_str())
-                                .unwrap_or(""),
-                            fb,
-                        );
-                        self.highlight_cache.push(HighlightSpan {
-                            start_byte: start,
-                            end_byte: end,
-                            color,
-                        });
+                        let overlaps_damage = damaged.iter().any(|(s, e)| end > *s && start < *e);
+                        if overlaps_damage {
+                            let color = theme.color_for_scope(
+                                highlight_names
+                                    .get(highlight_idx)
+                                    .map(|s| s.as_str())
+                                    .unwrap_or(""),
+                                fb,
+                            );
+                            fresh_spans.push(HighlightSpan {
+                                start_byte: start,
+                                end_byte: end,
+                                color,
+                            });
+                        }
                     }
                 }
                 Ok(HighlightEvent::HighlightStart(idx)) => {
@@ -284,7 +639,76 @@ impl SyntaxHighlighter {
             }
         }
 
-        self.highlight_cache_generation = self.buffer_generation;
+        Some(fresh_spans)
+    }
+
+    /// Ensure the rainbow bracket cache is up to date. No-op (and clears the
+    /// cache) when rainbow mode is off or the language has no rainbow query.
+    fn ensure_rainbow_cached(&mut self, fb: &Framebuffer) {
+        if !self.rainbow_enabled {
+            self.rainbow_cache.clear();
+            self.rainbow_cache_generation = None;
+            return;
+        }
+
+        // Generation equality alone is sufficient: `rainbow_cache.clear()`
+        // below always runs before a recompute, and turning rainbow mode off
+        // resets the generation to `None`, so an empty cache here just
+        // means the file legitimately has no rainbow matches (yet), not that
+        // the cache is stale.
+        if self.rainbow_cache_generation == Some(self.buffer_generation) {
+            return;
+        }
+
+        self.rainbow_cache.clear();
+
+        if self.rainbow_query.is_none() {
+            if let Some(query_source) = get_rainbow_query(self.language) {
+                if let Some(lang) = self.parser.language() {
+                    if let Ok(query) = tree_sitter::Query::new(&lang, query_source) {
+                        self.rainbow_query = Some(Arc::new(query));
+                    }
+                }
+            }
+        }
+
+        let (Some(tree), Some(query)) = (self.tree.as_ref(), self.rainbow_query.as_ref()) else {
+            return;
+        };
+
+        let scope_capture = query.capture_index_for_name("rainbow.scope");
+        let bracket_capture = query.capture_index_for_name("rainbow.bracket");
+
+        let mut scope_ranges: Vec<(usize, usize)> = Vec::new();
+        let mut bracket_ranges: Vec<(usize, usize)> = Vec::new();
+
+        let mut cursor = tree_sitter::QueryCursor::new();
+        let mut matches = cursor.matches(query, tree.root_node(), self.source_cache.as_slice());
+        while let Some(m) = matches.next() {
+            for capture in m.captures {
+                let index = Some(capture.index);
+                if index == scope_capture {
+                    scope_ranges.push((capture.node.start_byte(), capture.node.end_byte()));
+                } else if index == bracket_capture {
+                    bracket_ranges.push((capture.node.start_byte(), capture.node.end_byte()));
+                }
+            }
+        }
+
+        for (start, end) in bracket_ranges {
+            let depth = scope_ranges
+                .iter()
+                .filter(|(s, e)| *s <= start && end <= *e)
+                .count();
+            let color_index = rainbow_color_index(depth, RAINBOW_PALETTE.len());
+            self.rainbow_cache.push(HighlightSpan {
+                start_byte: start,
+                end_byte: end,
+                color: fb.indexed(RAINBOW_PALETTE[color_index]),
+            });
+        }
+
+        self.rainbow_cache_generation = Some(self.buffer_generation);
     }
 
     /// Get highlight spans for a byte range
@@ -292,13 +716,17 @@ impl SyntaxHighlighter {
         &mut self,
         start_byte: usize,
         end_byte: usize,
+        theme: &Theme,
         fb: &Framebuffer,
     ) -> Vec<HighlightSpan> {
-        self.ensure_highlights_cached(fb);
+        self.ensure_highlights_cached(theme, fb);
+        self.ensure_rainbow_cached(fb);
 
-        // Filter cached highlights to the requested range
+        // Filter cached highlights to the requested range. Rainbow spans are
+        // appended last so they're layered above the normal scope colors.
         self.highlight_cache
             .iter()
+            .chain(self.rainbow_cache.iter())
             .filter(|h| h.end_byte > start_byte && h.start_byte < end_byte)
             .map(|h| HighlightSpan {
                 start_byte: h.start_byte.max(start_byte),
@@ -313,6 +741,22 @@ impl SyntaxHighlighter {
     }
 }
 
+/// Look up a grammar compiled by `build.rs` via `GrammarRegistry` and adapt
+/// it to `get_language_config`'s return shape.
+fn grammar_language_config(
+    name: &str,
+) -> Result<(tree_sitter::Language, &'static str, Option<&'static str>, Option<&'static str>), String>
+{
+    let grammar = GrammarRegistry::by_name(name)
+        .ok_or_else(|| format!("Grammar {:?} is not registered", name))?;
+    Ok((
+        grammar.language(),
+        grammar.highlights_query,
+        grammar.injections_query,
+        grammar.locals_query,
+    ))
+}
+
 /// Get language configuration (parser, queries)
 fn get_language_config(
     language: Language,
@@ -411,12 +855,7 @@ fn get_language_config(
             None,
         )),
         #[cfg(feature = "syntax-kotlin")]
-        Language::Kotlin => Ok((
-            unsafe { tree_sitter_kotlin() },
-            KOTLIN_HIGHLIGHTS,
-            None,
-            None,
-        )),
+        Language::Kotlin => grammar_language_config("kotlin"),
         #[cfg(feature = "syntax-scala")]
         Language::Scala => Ok((
             tree_sitter_scala::LANGUAGE.into(),
@@ -460,12 +899,7 @@ fn get_language_config(
             None,
         )),
         #[cfg(feature = "syntax-markdown")]
-        Language::Markdown => Ok((
-            unsafe { tree_sitter_markdown() },
-            MARKDOWN_HIGHLIGHTS,
-            None,
-            None,
-        )),
+        Language::Markdown => grammar_language_config("markdown"),
         #[cfg(feature = "syntax-yaml")]
         Language::Yaml => Ok((
             tree_sitter_yaml::LANGUAGE.into(),
@@ -488,19 +922,9 @@ fn get_language_config(
             None,
         )),
         #[cfg(feature = "syntax-sql")]
-        Language::Sql => Ok((
-            unsafe { tree_sitter_sql() },
-            SQL_HIGHLIGHTS,
-            None,
-            None,
-        )),
+        Language::Sql => grammar_language_config("sql"),
         #[cfg(feature = "syntax-dockerfile")]
-        Language::Dockerfile => Ok((
-            unsafe { tree_sitter_dockerfile() },
-            DOCKERFILE_HIGHLIGHTS,
-            None,
-            None,
-        )),
+        Language::Dockerfile => grammar_language_config("dockerfile"),
         // Disabled languages (version incompatibility)
         Language::Toml => Err(format!(
             "Language {:?} is temporarily disabled (tree-sitter version incompatibility).",
@@ -541,25 +965,414 @@ fn get_language_config(
     }
 }
 
-/// Map tree-sitter highlight scope names to terminal colors
-fn get_highlight_color(scope: &str, fb: &Framebuffer) -> StraightRgba {
-    match scope {
-        "comment" => fb.indexed(IndexedColor::BrightBlack),
-        "keyword" => fb.indexed(IndexedColor::Magenta),
-        "function" | "function.method" | "function.builtin" => fb.indexed(IndexedColor::Blue),
-        "string" | "string.special" => fb.indexed(IndexedColor::Green),
-        "type" | "type.builtin" => fb.indexed(IndexedColor::Cyan),
-        "constant" | "constant.builtin" | "number" => fb.indexed(IndexedColor::Yellow),
-        "variable.parameter" => fb.indexed(IndexedColor::BrightCyan),
-        "operator" => fb.indexed(IndexedColor::BrightWhite),
-        "property" => fb.indexed(IndexedColor::BrightBlue),
-        "attribute" => fb.indexed(IndexedColor::BrightYellow),
-        "constructor" => fb.indexed(IndexedColor::BrightMagenta),
-        "tag" => fb.indexed(IndexedColor::Red),
-        "punctuation" | "punctuation.bracket" | "punctuation.delimiter" => {
-            fb.indexed(IndexedColor::Foreground)
+/// Get the embedded rainbow-bracket query for a language, if one exists.
+/// Rainbow highlighting is opt-in, so languages without a query simply don't
+/// participate rather than falling back to anything.
+fn get_rainbow_query(language: Language) -> Option<&'static str> {
+    match language {
+        Language::Rust => Some(RUST_RAINBOWS),
+        Language::JavaScript | Language::TypeScript => Some(JAVASCRIPT_RAINBOWS),
+        Language::Python => Some(PYTHON_RAINBOWS),
+        Language::Json => Some(JSON_RAINBOWS),
+        _ => None,
+    }
+}
+
+/// Colors cycled by nesting depth (`(depth - 1) % RAINBOW_PALETTE.len()` for
+/// the 1-based depth, see `rainbow_color_index`) when rainbow bracket mode is
+/// enabled.
+const RAINBOW_PALETTE: &[IndexedColor] = &[
+    IndexedColor::Red,
+    IndexedColor::Yellow,
+    IndexedColor::Green,
+    IndexedColor::Cyan,
+    IndexedColor::Blue,
+    IndexedColor::Magenta,
+];
+
+/// Maps a bracket's nesting depth to a `RAINBOW_PALETTE` index, cycling once
+/// the palette is exhausted. Depth is 1-based (a top-level bracket pair has
+/// depth 1), so it's shifted down by one before the modulo.
+fn rainbow_color_index(depth: usize, palette_len: usize) -> usize {
+    depth.saturating_sub(1) % palette_len
+}
+
+/// A color entry in a `Theme`: either one of the terminal's existing indexed
+/// colors, or a direct 24-bit RGB value for themes that want exact colors.
+#[derive(Debug, Clone, Copy)]
+pub enum ThemeColor {
+    Indexed(IndexedColor),
+    Rgb(StraightRgba),
+}
+
+impl ThemeColor {
+    fn resolve(self, fb: &Framebuffer) -> StraightRgba {
+        match self {
+            ThemeColor::Indexed(color) => fb.indexed(color),
+            ThemeColor::Rgb(rgba) => rgba,
+        }
+    }
+}
+
+/// Maps tree-sitter highlight scope names (e.g. `variable.parameter`) to
+/// colors. Tree-sitter scopes are hierarchical, so lookups fall back through
+/// dot-separated prefixes (`variable.parameter` -> `variable` -> the theme's
+/// default) instead of requiring every scope to be listed explicitly.
+pub struct Theme {
+    scopes: HashMap<String, ThemeColor>,
+    default: ThemeColor,
+}
+
+impl Theme {
+    /// The built-in theme, matching this editor's previous hardcoded colors.
+    pub fn builtin() -> Self {
+        let entries: &[(&str, IndexedColor)] = &[
+            ("comment", IndexedColor::BrightBlack),
+            ("keyword", IndexedColor::Magenta),
+            ("function", IndexedColor::Blue),
+            ("function.method", IndexedColor::Blue),
+            ("function.builtin", IndexedColor::Blue),
+            ("string", IndexedColor::Green),
+            ("string.special", IndexedColor::Green),
+            ("type", IndexedColor::Cyan),
+            ("type.builtin", IndexedColor::Cyan),
+            ("constant", IndexedColor::Yellow),
+            ("constant.builtin", IndexedColor::Yellow),
+            ("number", IndexedColor::Yellow),
+            ("variable.parameter", IndexedColor::BrightCyan),
+            ("operator", IndexedColor::BrightWhite),
+            ("property", IndexedColor::BrightBlue),
+            ("attribute", IndexedColor::BrightYellow),
+            ("constructor", IndexedColor::BrightMagenta),
+            ("tag", IndexedColor::Red),
+        ];
+
+        let scopes = entries
+            .iter()
+            .map(|(scope, color)| (scope.to_string(), ThemeColor::Indexed(*color)))
+            .collect();
+
+        Self {
+            scopes,
+            default: ThemeColor::Indexed(IndexedColor::Foreground),
+        }
+    }
+
+    /// Load a theme from a TOML file of `scope = "color"` entries, where
+    /// `color` is either the name of an indexed palette color (`"cyan"`,
+    /// `"bright_blue"`) or a `"#rrggbb"` hex string. An optional top-level
+    /// `default = "..."` entry overrides the fallback color.
+    pub fn load_from_toml(path: &std::path::Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read theme file {}: {}", path.display(), e))?;
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e| format!("Failed to parse theme file {}: {}", path.display(), e))?;
+
+        let mut theme = Theme::builtin();
+        for (scope, value) in &table {
+            let Some(value) = value.as_str() else { continue };
+            if let Some(color) = parse_theme_color(value) {
+                if scope == "default" {
+                    theme.default = color;
+                } else {
+                    theme.scopes.insert(scope.clone(), color);
+                }
+            }
+        }
+
+        Ok(theme)
+    }
+
+    /// Resolve a highlight scope name to a color, falling back through
+    /// dot-separated prefixes and finally to the theme's default.
+    pub fn color_for_scope(&self, scope: &str, fb: &Framebuffer) -> StraightRgba {
+        lookup_scope(&self.scopes, scope)
+            .unwrap_or(&self.default)
+            .resolve(fb)
+    }
+}
+
+/// Looks up `scope` in `scopes`, falling back through dot-separated prefixes
+/// (`variable.parameter` -> `variable`) until one matches or the prefixes run
+/// out. Split out from `Theme::color_for_scope` so the fallback logic can be
+/// tested without a `Framebuffer`.
+fn lookup_scope<'a>(scopes: &'a HashMap<String, ThemeColor>, scope: &str) -> Option<&'a ThemeColor> {
+    let mut candidate = scope;
+    loop {
+        if let Some(color) = scopes.get(candidate) {
+            return Some(color);
         }
-        "variable" | "variable.builtin" => fb.indexed(IndexedColor::Foreground),
-        _ => fb.indexed(IndexedColor::Foreground),
+        match candidate.rfind('.') {
+            Some(idx) => candidate = &candidate[..idx],
+            None => return None,
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::builtin()
+    }
+}
+
+fn parse_theme_color(value: &str) -> Option<ThemeColor> {
+    if let Some(hex) = value.strip_prefix('#') {
+        return parse_hex_rgb(hex).map(ThemeColor::Rgb);
+    }
+    indexed_color_from_name(value).map(ThemeColor::Indexed)
+}
+
+fn parse_hex_rgb(hex: &str) -> Option<StraightRgba> {
+    if hex.len() != 6 {
+        return None;
+    }
+    let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+    let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+    let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+    Some(StraightRgba::new(r, g, b, 255))
+}
+
+fn indexed_color_from_name(name: &str) -> Option<IndexedColor> {
+    match name.to_lowercase().replace('-', "_").as_str() {
+        "black" => Some(IndexedColor::Black),
+        "red" => Some(IndexedColor::Red),
+        "green" => Some(IndexedColor::Green),
+        "yellow" => Some(IndexedColor::Yellow),
+        "blue" => Some(IndexedColor::Blue),
+        "magenta" => Some(IndexedColor::Magenta),
+        "cyan" => Some(IndexedColor::Cyan),
+        "white" => Some(IndexedColor::White),
+        "bright_black" => Some(IndexedColor::BrightBlack),
+        "bright_red" => Some(IndexedColor::BrightRed),
+        "bright_green" => Some(IndexedColor::BrightGreen),
+        "bright_yellow" => Some(IndexedColor::BrightYellow),
+        "bright_blue" => Some(IndexedColor::BrightBlue),
+        "bright_magenta" => Some(IndexedColor::BrightMagenta),
+        "bright_cyan" => Some(IndexedColor::BrightCyan),
+        "bright_white" => Some(IndexedColor::BrightWhite),
+        "foreground" => Some(IndexedColor::Foreground),
+        "background" => Some(IndexedColor::Background),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, end: usize) -> HighlightSpan {
+        HighlightSpan {
+            start_byte: start,
+            end_byte: end,
+            color: StraightRgba::new(0, 0, 0, 255),
+        }
+    }
+
+    #[test]
+    fn reconcile_spans_drops_spans_overlapping_a_changed_range() {
+        let spans = vec![span(0, 5), span(5, 10), span(10, 15)];
+        let result = reconcile_spans(spans, &[(4, 11)], 10, 0);
+        assert_eq!(
+            result.iter().map(|s| (s.start_byte, s.end_byte)).collect::<Vec<_>>(),
+            vec![(0, 5), (10, 15)]
+        );
+    }
+
+    #[test]
+    fn reconcile_spans_shifts_spans_after_the_edit() {
+        // Inserted 3 bytes at byte 10; a span starting at byte 20 should
+        // shift to start at byte 23, one starting before the edit shouldn't.
+        let spans = vec![span(0, 5), span(20, 25)];
+        let result = reconcile_spans(spans, &[], 10, 3);
+        assert_eq!(
+            result.iter().map(|s| (s.start_byte, s.end_byte)).collect::<Vec<_>>(),
+            vec![(0, 5), (23, 28)]
+        );
+    }
+
+    #[test]
+    fn reconcile_spans_shifts_back_on_deletion() {
+        // Deleted 4 bytes ending at byte 10; a span starting at byte 10
+        // should shift left to byte 6.
+        let spans = vec![span(10, 15)];
+        let result = reconcile_spans(spans, &[], 10, -4);
+        assert_eq!(
+            result.iter().map(|s| (s.start_byte, s.end_byte)).collect::<Vec<_>>(),
+            vec![(6, 11)]
+        );
+    }
+
+    #[test]
+    fn reconcile_spans_checks_overlap_in_post_edit_coordinates() {
+        // Insert 1000 bytes at old offset 50. A span that falls after the
+        // edit point must be shifted into new-tree coordinates *before*
+        // it's checked against `changed_ranges` (which `Tree::changed_ranges`
+        // already reports in new-tree coordinates) -- otherwise a stale span
+        // can slip through as "not overlapping" when it actually does.
+        let overlapping = span(500, 505); // shifts to (1500, 1505)
+        let untouched = span(3000, 3005); // shifts to (4000, 4005)
+        let spans = vec![overlapping, untouched];
+        let result = reconcile_spans(spans, &[(1495, 1510)], 50, 1000);
+        assert_eq!(
+            result.iter().map(|s| (s.start_byte, s.end_byte)).collect::<Vec<_>>(),
+            vec![(4000, 4005)]
+        );
+    }
+
+    #[test]
+    fn rainbow_color_index_cycles_through_the_palette() {
+        assert_eq!(rainbow_color_index(1, 6), 0);
+        assert_eq!(rainbow_color_index(6, 6), 5);
+        assert_eq!(rainbow_color_index(7, 6), 0);
+    }
+
+    #[test]
+    fn rainbow_color_index_treats_zero_depth_as_first_color() {
+        assert_eq!(rainbow_color_index(0, 6), 0);
+    }
+
+    #[test]
+    fn parse_hex_rgb_parses_a_six_digit_hex_string() {
+        let color = parse_hex_rgb("ff8000").unwrap();
+        assert_eq!(format!("{:?}", color), format!("{:?}", StraightRgba::new(0xff, 0x80, 0x00, 255)));
+    }
+
+    #[test]
+    fn parse_hex_rgb_rejects_the_wrong_length() {
+        assert!(parse_hex_rgb("fff").is_none());
+        assert!(parse_hex_rgb("ff80000").is_none());
+    }
+
+    #[test]
+    fn parse_hex_rgb_rejects_non_hex_digits() {
+        assert!(parse_hex_rgb("zzzzzz").is_none());
+    }
+
+    #[test]
+    fn indexed_color_from_name_is_case_and_separator_insensitive() {
+        assert!(matches!(indexed_color_from_name("cyan"), Some(IndexedColor::Cyan)));
+        assert!(matches!(indexed_color_from_name("Bright-Blue"), Some(IndexedColor::BrightBlue)));
+        assert!(indexed_color_from_name("not-a-color").is_none());
+    }
+
+    #[test]
+    fn parse_theme_color_dispatches_hex_vs_named() {
+        assert!(matches!(parse_theme_color("#00ff00"), Some(ThemeColor::Rgb(_))));
+        assert!(matches!(parse_theme_color("green"), Some(ThemeColor::Indexed(IndexedColor::Green))));
+        assert!(parse_theme_color("not-a-color").is_none());
+    }
+
+    #[test]
+    fn lookup_scope_falls_back_through_dotted_prefixes() {
+        let mut scopes = HashMap::new();
+        scopes.insert("variable".to_string(), ThemeColor::Indexed(IndexedColor::Cyan));
+        scopes.insert("function.method".to_string(), ThemeColor::Indexed(IndexedColor::Blue));
+
+        // Exact match wins.
+        assert!(matches!(
+            lookup_scope(&scopes, "function.method"),
+            Some(ThemeColor::Indexed(IndexedColor::Blue))
+        ));
+        // Falls back from `variable.parameter` to `variable`.
+        assert!(matches!(
+            lookup_scope(&scopes, "variable.parameter"),
+            Some(ThemeColor::Indexed(IndexedColor::Cyan))
+        ));
+        // No match anywhere in the prefix chain.
+        assert!(lookup_scope(&scopes, "keyword.operator").is_none());
+    }
+
+    #[test]
+    fn load_from_toml_overrides_builtin_scopes_and_default() {
+        let path = std::env::temp_dir().join(format!(
+            "gordon8214-edit-theme-test-{:?}.toml",
+            std::thread::current().id()
+        ));
+        std::fs::write(
+            &path,
+            "default = \"#112233\"\ncomment = \"red\"\nnot_a_color = \"nonsense\"\n",
+        )
+        .unwrap();
+
+        let theme = Theme::load_from_toml(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert!(matches!(
+            lookup_scope(&theme.scopes, "comment"),
+            Some(ThemeColor::Indexed(IndexedColor::Red))
+        ));
+        assert!(matches!(theme.default, ThemeColor::Rgb(_)));
+        // Unrecognized values are silently skipped rather than erroring the
+        // whole file, so other scopes (here, the builtin default for
+        // "keyword") are left untouched.
+        assert!(lookup_scope(&theme.scopes, "not_a_color").is_none());
+        assert!(matches!(
+            lookup_scope(&theme.scopes, "keyword"),
+            Some(ThemeColor::Indexed(IndexedColor::Magenta))
+        ));
+    }
+
+    #[test]
+    fn load_from_toml_reports_an_error_for_a_missing_file() {
+        let path = std::env::temp_dir().join("gordon8214-edit-theme-test-missing.toml");
+        assert!(Theme::load_from_toml(&path).is_err());
+    }
+
+    #[test]
+    fn from_content_detects_a_python_shebang() {
+        assert_eq!(
+            Language::from_content("#!/usr/bin/env python3\nprint('hi')\n"),
+            Some(Language::Python)
+        );
+    }
+
+    #[test]
+    fn from_content_detects_a_bash_shebang_without_env() {
+        assert_eq!(
+            Language::from_content("#!/bin/bash\necho hi\n"),
+            Some(Language::Bash)
+        );
+    }
+
+    #[test]
+    fn from_content_detects_a_vim_modeline() {
+        assert_eq!(
+            Language::from_content("fn main() {}\n// vim: set ft=rust:\n"),
+            Some(Language::Rust)
+        );
+    }
+
+    #[test]
+    fn from_content_detects_an_emacs_modeline() {
+        assert_eq!(
+            Language::from_content("-*- mode: python -*-\nprint('hi')\n"),
+            Some(Language::Python)
+        );
+    }
+
+    #[test]
+    fn from_content_returns_none_for_unrecognized_content() {
+        assert_eq!(Language::from_content("just some plain text\n"), None);
+    }
+
+    #[test]
+    fn from_shebang_strips_trailing_version_digits() {
+        assert_eq!(Language::from_shebang("/usr/bin/env python3.11"), Some(Language::Python));
+        assert_eq!(Language::from_shebang("/usr/bin/ruby"), Some(Language::Ruby));
+        assert_eq!(Language::from_shebang("/usr/bin/env unknown-interpreter"), None);
+    }
+
+    #[test]
+    fn from_modeline_finds_vim_filetype_among_other_tokens() {
+        assert_eq!(
+            Language::from_modeline("/* vim: set ft=json ts=2 sw=2: */"),
+            Some(Language::Json)
+        );
+    }
+
+    #[test]
+    fn from_modeline_ignores_non_modeline_text() {
+        assert_eq!(Language::from_modeline("this is not a modeline"), None);
     }
 }